@@ -0,0 +1,112 @@
+use crate::state::KernelHandle;
+
+use memflow_win32::*;
+
+/// A neutral, backend-agnostic process descriptor.
+pub struct ProcInfo {
+    pub pid: i32,
+    pub name: String,
+    pub arch: String,
+}
+
+/// A neutral, backend-agnostic module descriptor.
+pub struct ModInfo {
+    pub base: u64,
+    pub size: u64,
+    pub name: String,
+}
+
+/// Decouples the VMFS folder/file building logic from the concrete kernel
+/// connector. Implemented once per memflow connector family (Win32 today,
+/// potentially Linux/KVM later) so `VirtualMemoryFileSystem` and its modules
+/// never have to match on `KernelHandle` variants themselves.
+pub trait VmfsProcessSource {
+    fn process_list(&mut self) -> Result<Vec<ProcInfo>, String>;
+    fn module_list(&mut self, pid: i32) -> Result<Vec<ModInfo>, String>;
+    fn read(&mut self, pid: i32, addr: u64, size: u32) -> Result<Vec<u8>, String>;
+    fn write(&mut self, pid: i32, addr: u64, data: &[u8]) -> Result<i64, String>;
+}
+
+impl VmfsProcessSource for KernelHandle {
+    fn process_list(&mut self) -> Result<Vec<ProcInfo>, String> {
+        match self {
+            KernelHandle::Win32(kernel) => Ok(kernel
+                .process_info_list()
+                .map_err(|err| format!("{:?}", err))?
+                .iter()
+                .map(|pi| ProcInfo {
+                    pid: pi.pid,
+                    name: pi.name.clone(),
+                    arch: format!("{:?}", pi.sys_arch),
+                })
+                .collect()),
+        }
+    }
+
+    fn module_list(&mut self, pid: i32) -> Result<Vec<ModInfo>, String> {
+        match self {
+            KernelHandle::Win32(kernel) => {
+                let pi = kernel
+                    .process_info_list()
+                    .map_err(|err| format!("{:?}", err))?
+                    .into_iter()
+                    .find(|pi| pi.pid == pid)
+                    .ok_or_else(|| format!("no such process: {}", pid))?;
+
+                let mut process = kernel.process(pi).map_err(|err| format!("{:?}", err))?;
+
+                Ok(process
+                    .module_list()
+                    .map_err(|err| format!("{:?}", err))?
+                    .iter()
+                    .map(|module| ModInfo {
+                        base: module.base.as_u64(),
+                        size: module.size as u64,
+                        name: module.name.clone(),
+                    })
+                    .collect())
+            }
+        }
+    }
+
+    fn read(&mut self, pid: i32, addr: u64, size: u32) -> Result<Vec<u8>, String> {
+        match self {
+            KernelHandle::Win32(kernel) => {
+                let pi = kernel
+                    .process_info_list()
+                    .map_err(|err| format!("{:?}", err))?
+                    .into_iter()
+                    .find(|pi| pi.pid == pid)
+                    .ok_or_else(|| format!("no such process: {}", pid))?;
+
+                let mut process = kernel.process(pi).map_err(|err| format!("{:?}", err))?;
+
+                let mut buf = vec![0u8; size as usize];
+                process
+                    .virt_read_raw_into(Address::from(addr), &mut buf)
+                    .map_err(|err| format!("{:?}", err))?;
+                Ok(buf)
+            }
+        }
+    }
+
+    fn write(&mut self, pid: i32, addr: u64, data: &[u8]) -> Result<i64, String> {
+        match self {
+            KernelHandle::Win32(kernel) => {
+                let pi = kernel
+                    .process_info_list()
+                    .map_err(|err| format!("{:?}", err))?
+                    .into_iter()
+                    .find(|pi| pi.pid == pid)
+                    .ok_or_else(|| format!("no such process: {}", pid))?;
+
+                let mut process = kernel.process(pi).map_err(|err| format!("{:?}", err))?;
+
+                process
+                    .virt_write_raw(Address::from(addr), data)
+                    .map_err(|err| format!("{:?}", err))?;
+                Ok(data.len() as i64)
+            }
+        }
+    }
+}
@@ -1,24 +1,42 @@
+mod backend;
+mod maps;
+mod mem;
+mod module;
 mod process_info;
+mod status;
+pub mod virtiofs;
 
-use crate::state::{state_lock_sync, KernelHandle};
+use crate::state::state_lock_sync;
+use backend::{ModInfo, ProcInfo, VmfsProcessSource};
 
 use bitfield::bitfield;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::time::{Duration, Instant, UNIX_EPOCH};
 
-use log::{info, trace};
-
-use memflow_win32::*;
+use log::{info, trace, warn};
 
 use fuse::{
-    FileAttr, FileType, Filesystem, ReplyAttr, ReplyData, ReplyDirectory, ReplyEntry, Request,
+    FileAttr, FileType, Filesystem, ReplyAttr, ReplyData, ReplyDirectory, ReplyEntry, ReplyWrite,
+    Request,
 };
-use libc::ENOENT;
+use libc::{EACCES, EIO, EISDIR, ENOENT};
 use std::ffi::OsStr;
+use std::io;
+use std::path::Path;
 
 /// Default file TTL
 const TTL: Duration = Duration::from_secs(1);
 
+/// Permission bits for a file: writable files (those with a `write` callback)
+/// are `0o666`, read-only files are `0o644`.
+fn file_perm(file: &VirtualFile) -> u16 {
+    if file.write.is_some() {
+        0o666
+    } else {
+        0o644
+    }
+}
+
 /// Describes an INode in the VMFS
 /// This bitfield struct is used to ensure that processes with the same PID end up with the same inodes.
 bitfield! {
@@ -73,9 +91,13 @@ pub struct VirtualFile {
     pub inode: u64,
     pub name: String,
 
-    // TODO: callbacks for size/contents/etc
     pub content_length: Box<dyn Fn() -> u64>,
-    pub contents: Box<dyn Fn() -> Vec<u8>>,
+    // reads the window [offset..offset + size) of the file's contents, bounded by content_length;
+    // `Err` surfaces a real I/O failure (e.g. an unmapped page) rather than faking an empty read
+    pub contents: Box<dyn Fn(u64, u32) -> io::Result<Vec<u8>>>,
+    // writes `data` at `offset`, returning the number of bytes written; `None` makes the file
+    // read-only, `Err` surfaces a real I/O failure rather than faking a 0-byte write
+    pub write: Option<Box<dyn Fn(u64, &[u8]) -> io::Result<i64>>>,
 }
 
 /// The scope a vmfs module uses.
@@ -91,7 +113,7 @@ pub enum VMFSModuleScope {
 pub enum VMFSScopeContext {
     Connection { conn_id: String },
     Process { conn_id: String, pid: i32 },
-    Module { conn_id: String, pid: i32, mid: u8 },
+    Module { conn_id: String, pid: i32, base: u64 },
 }
 
 /// Trait describing a module of the vmfs.
@@ -101,6 +123,54 @@ pub trait VMFSModule {
     fn entry(&self, inode: u64, ctx: VMFSScopeContext) -> VirtualEntry;
 }
 
+/// The transport a `VirtualMemoryFileSystem` is served over.
+pub enum MountMode {
+    /// A local libfuse mount via `/dev/fuse` (the `Filesystem` impl below).
+    Fuse,
+    /// A vhost-user virtio-fs device, listening on a unix socket, so a VM
+    /// can mount the tree directly (see the `virtiofs` module).
+    VirtioFs { socket_path: String },
+}
+
+/// Serves `vmfs` over the transport selected by `mode`. `mountpoint` is only
+/// used for `MountMode::Fuse` - a virtio-fs guest mounts the tree itself,
+/// from inside the VM, once `serve()` is listening on its socket.
+pub fn mount(vmfs: VirtualMemoryFileSystem, mode: MountMode, mountpoint: &Path) -> io::Result<()> {
+    match mode {
+        MountMode::Fuse => fuse::mount(vmfs, mountpoint, &[]),
+        MountMode::VirtioFs { socket_path } => virtiofs::serve(vmfs, &socket_path),
+    }
+}
+
+/// Builds the vmfs for `conn_id` and mounts it at `mountpoint`, picking the
+/// transport via `virtiofs_socket`: `Some(path)` serves a vhost-user
+/// virtio-fs device on that socket, `None` falls back to a local libfuse
+/// mount. This is the single function a `mount` command's CLI/config layer
+/// should call - it's the actual point where `MountMode` gets chosen, rather
+/// than leaving callers to construct `MountMode` (and decide between `mount`
+/// and a bare `VirtualMemoryFileSystem::new`) themselves.
+///
+/// NOTE: this trimmed tree doesn't include the daemon's command-dispatch
+/// layer (the place a `--virtiofs-socket` flag or config value would be
+/// parsed), so nothing calls this yet; it exists so that layer has exactly
+/// one function to wire up once it's restored, instead of `mount()` being
+/// reachable with no real way to pick its `mode` argument.
+pub fn spawn_and_mount(
+    id: &str,
+    conn_id: &str,
+    uid: u32,
+    gid: u32,
+    mountpoint: &Path,
+    virtiofs_socket: Option<String>,
+) -> io::Result<()> {
+    let vmfs = VirtualMemoryFileSystem::new(id, conn_id, uid, gid);
+    let mode = match virtiofs_socket {
+        Some(socket_path) => MountMode::VirtioFs { socket_path },
+        None => MountMode::Fuse,
+    };
+    mount(vmfs, mode, mountpoint)
+}
+
 /// The Virtual Memory File System
 /// ...
 pub struct VirtualMemoryFileSystem {
@@ -113,6 +183,12 @@ pub struct VirtualMemoryFileSystem {
     last_refresh: Instant,
     file_system: HashMap<u64, VirtualEntry>,
 
+    // tracks the live pid set so `update_file_system` can diff against it
+    // instead of rebuilding the whole tree (and invalidating every inode)
+    // on every refresh.
+    pid_inodes: HashMap<i32, u64>,
+    pid_modules: HashMap<i32, ModulesKey>,
+
     modules_connections: Vec<Box<dyn VMFSModule>>,
     modules_processes: Vec<Box<dyn VMFSModule>>,
     modules_modules: Vec<Box<dyn VMFSModule>>,
@@ -120,6 +196,44 @@ pub struct VirtualMemoryFileSystem {
 
 unsafe impl Send for VirtualMemoryFileSystem {}
 
+/// A comparable snapshot of a process's module list, used to detect whether
+/// its `modules/` folder needs to be rebuilt.
+type ModulesKey = Vec<(u64, u64, String)>;
+
+/// Pids tracked in `pid_inodes` that aren't in `live_pids` - i.e. processes
+/// that have exited since the last refresh. Pulled out of `refresh_pids` so
+/// the diff itself can be unit-tested without a live kernel connection.
+fn diff_stale_pids(live_pids: &HashSet<i32>, tracked_pids: impl Iterator<Item = i32>) -> Vec<i32> {
+    tracked_pids
+        .filter(|pid| !live_pids.contains(pid))
+        .collect()
+}
+
+/// What `refresh_pids` should do with a still-live pid, given what (if
+/// anything) it already knows about it.
+#[derive(Debug, PartialEq, Eq)]
+enum PidAction {
+    /// The pid's module list hasn't changed; leave its subtree untouched.
+    Unchanged,
+    /// The pid's module list changed; rebuild its subtree, reusing the
+    /// given (pid-derived) process folder inode.
+    Changed(u64),
+    /// The pid wasn't tracked before; create a new subtree for it.
+    New,
+}
+
+fn classify_pid(
+    existing_inode: Option<u64>,
+    existing_modules: Option<&ModulesKey>,
+    new_modules: &ModulesKey,
+) -> PidAction {
+    match existing_inode {
+        Some(_) if existing_modules == Some(new_modules) => PidAction::Unchanged,
+        Some(prc_inode) => PidAction::Changed(prc_inode),
+        None => PidAction::New,
+    }
+}
+
 impl VirtualMemoryFileSystem {
     pub fn new(id: &str, conn_id: &str, uid: u32, gid: u32) -> Self {
         let mut fs = Self {
@@ -132,51 +246,158 @@ impl VirtualMemoryFileSystem {
             last_refresh: Instant::now(),
             file_system: HashMap::new(),
 
+            pid_inodes: HashMap::new(),
+            pid_modules: HashMap::new(),
+
             modules_connections: Vec::new(),
-            modules_processes: vec![Box::new(process_info::VMFSProcessInfo)],
-            modules_modules: Vec::new(),
+            modules_processes: vec![
+                Box::new(process_info::VMFSProcessInfo),
+                Box::new(status::VMFSStatus),
+                Box::new(maps::VMFSMaps),
+                Box::new(mem::VMFSMem),
+            ],
+            modules_modules: vec![
+                Box::new(module::VMFSModuleBase),
+                Box::new(module::VMFSModuleSize),
+                Box::new(module::VMFSModuleName),
+            ],
         };
 
-        // initialize file_system
-        fs.file_system = fs.create_root_folder();
+        // initialize file_system; pid_inodes/pid_modules start out empty, so
+        // this first refresh treats every live pid as new.
+        fs.refresh_pids();
 
         fs
     }
 
     fn update_file_system(&mut self) {
         if self.last_refresh.elapsed() > Duration::from_secs(10) {
-            self.file_system = self.create_root_folder();
+            self.refresh_pids();
             self.last_refresh = Instant::now();
         }
     }
 
-    fn create_root_folder(&self) -> HashMap<u64, VirtualEntry> {
-        // TODO: incremental updates for changed pids
-        let mut fs = HashMap::new();
+    /// Diffs the live pid set against `pid_inodes`: subtrees of pids that
+    /// disappeared are removed, subtrees for new pids are added, and pids
+    /// that are still alive keep their inode untouched unless their module
+    /// list changed - unlike a full rebuild, this keeps the kernel's FUSE
+    /// inode cache (`TTL`) warm for every process that hasn't changed.
+    ///
+    /// A failed `process_list()` skips the whole refresh cycle rather than
+    /// treating the error as "no processes are running", which would tear
+    /// down every subtree on a single transient kernel read failure. A
+    /// failed `module_list()` for one pid similarly just leaves that pid's
+    /// existing subtree in place for this cycle instead of rebuilding it
+    /// from an empty list.
+    fn refresh_pids(&mut self) {
+        let mut state = state_lock_sync();
+        let conn = match state.connection_mut(&self.conn_id) {
+            Some(conn) => conn,
+            None => return,
+        };
 
-        let mut root = VirtualFolder::new(0, &self.conn_id);
+        let processes = match conn.kernel.process_list() {
+            Ok(processes) => processes,
+            Err(err) => {
+                warn!("refresh_pids(): process_list() failed, skipping this refresh: {}", err);
+                return;
+            }
+        };
+        let modules: Vec<Option<Vec<ModInfo>>> = processes
+            .iter()
+            .map(|pi| match conn.kernel.module_list(pi.pid) {
+                Ok(modules) => Some(modules),
+                Err(err) => {
+                    warn!(
+                        "refresh_pids(): module_list({}) failed, keeping existing subtree: {}",
+                        pi.pid, err
+                    );
+                    None
+                }
+            })
+            .collect();
+        drop(state);
 
-        let mut state = state_lock_sync();
-        if let Some(conn) = state.connection_mut(&self.conn_id) {
-            match &mut conn.kernel {
-                KernelHandle::Win32(kernel) => {
-                    if let Ok(process_info) = kernel.process_info_list() {
-                        for pi in process_info.iter() {
-                            root.children.push(self.create_process_folder(pi, &mut fs));
+        if !self.file_system.contains_key(&0) {
+            self.file_system
+                .insert(0, VirtualEntry::Folder(VirtualFolder::new(0, &self.conn_id)));
+        }
+
+        let live_pids: HashSet<i32> = processes.iter().map(|pi| pi.pid).collect();
+
+        // remove subtrees of pids that disappeared
+        for pid in diff_stale_pids(&live_pids, self.pid_inodes.keys().copied()) {
+            if let Some(prc_inode) = self.pid_inodes.remove(&pid) {
+                Self::remove_subtree(&mut self.file_system, prc_inode);
+                if let Some(VirtualEntry::Folder(root)) = self.file_system.get_mut(&0) {
+                    root.children.retain(|&child| child != prc_inode);
+                }
+            }
+            self.pid_modules.remove(&pid);
+        }
+
+        // add new pids, and rebuild the subtree of pids whose module list changed
+        let mut fs = std::mem::take(&mut self.file_system);
+
+        for (pi, modules) in processes.iter().zip(modules.into_iter()) {
+            let modules = match modules {
+                Some(modules) => modules,
+                None => continue,
+            };
+
+            let modules_key: ModulesKey = modules
+                .iter()
+                .map(|module| (module.base, module.size, module.name.clone()))
+                .collect();
+
+            match classify_pid(
+                self.pid_inodes.get(&pi.pid).copied(),
+                self.pid_modules.get(&pi.pid),
+                &modules_key,
+            ) {
+                PidAction::Unchanged => {
+                    // leave the existing subtree (and its inodes) in place
+                }
+                PidAction::Changed(prc_inode) => {
+                    // module list changed: rebuild just this subtree, reusing the
+                    // same (pid-derived) inode for the process folder itself
+                    Self::remove_subtree(&mut fs, prc_inode);
+                    let new_inode = self.create_process_folder(pi, &modules, &mut fs);
+                    if let Some(VirtualEntry::Folder(root)) = fs.get_mut(&0) {
+                        if !root.children.contains(&new_inode) {
+                            root.children.push(new_inode);
                         }
                     }
                 }
+                PidAction::New => {
+                    // brand new process
+                    let prc_inode = self.create_process_folder(pi, &modules, &mut fs);
+                    self.pid_inodes.insert(pi.pid, prc_inode);
+                    if let Some(VirtualEntry::Folder(root)) = fs.get_mut(&0) {
+                        root.children.push(prc_inode);
+                    }
+                }
             }
+
+            self.pid_modules.insert(pi.pid, modules_key);
         }
 
-        fs.insert(root.inode, VirtualEntry::Folder(root));
+        self.file_system = fs;
+    }
 
-        fs
+    /// Recursively removes an entry and, if it's a folder, all of its children.
+    fn remove_subtree(fs: &mut HashMap<u64, VirtualEntry>, inode: u64) {
+        if let Some(VirtualEntry::Folder(folder)) = fs.remove(&inode) {
+            for child in folder.children {
+                Self::remove_subtree(fs, child);
+            }
+        }
     }
 
     fn create_process_folder(
         &self,
-        pi: &Win32ProcessInfo,
+        pi: &ProcInfo,
+        modules: &[ModInfo],
         fs: &mut HashMap<u64, VirtualEntry>,
     ) -> u64 {
         let mut inode = INode(0);
@@ -192,7 +413,7 @@ impl VirtualMemoryFileSystem {
             pid: pi.pid,
         };
 
-        // add module scope
+        // add process scope files (process_info, status, maps, ...)
         for module in self.modules_processes.iter() {
             // instantiate entry
             inode.set_mid(inode.mid() + 1);
@@ -203,155 +424,244 @@ impl VirtualMemoryFileSystem {
             prc.children.push(inode.0);
         }
 
+        // add the modules/ subfolder, one child folder per loaded module
+        inode.set_mid(inode.mid() + 1);
+        let modules_inode =
+            self.create_modules_folder(pi.pid, modules, &mut inode, fs);
+        prc.children.push(modules_inode);
+
         let prc_inode = prc.inode;
         fs.insert(prc_inode, VirtualEntry::Folder(prc));
         prc_inode
     }
+
+    /// Builds the `modules/` folder of a process, with one child folder per
+    /// loaded module (named `<mid>_<name>`), each populated by `modules_modules`.
+    fn create_modules_folder(
+        &self,
+        pid: i32,
+        modules: &[ModInfo],
+        inode: &mut INode,
+        fs: &mut HashMap<u64, VirtualEntry>,
+    ) -> u64 {
+        let modules_folder_inode = inode.0;
+        let mut modules_folder = VirtualFolder::new(modules_folder_inode, "modules");
+
+        for (mid, module) in modules.iter().enumerate() {
+            inode.set_mid(inode.mid() + 1);
+            let module_folder_inode = inode.0;
+
+            let mut module_folder = VirtualFolder::new(
+                module_folder_inode,
+                &format!("{}_{}", mid, module.name.replace(".", "_")),
+            );
+
+            // identify the module by its base address rather than its position
+            // in `modules` - `mid` is only a stable label for this folder's
+            // name, not something safe to re-derive from a fresh
+            // `module_list()` call at read time (see `find_module`).
+            let ctx = VMFSScopeContext::Module {
+                conn_id: self.conn_id.clone(),
+                pid,
+                base: module.base,
+            };
+
+            for vmfs_module in self.modules_modules.iter() {
+                inode.set_mid(inode.mid() + 1);
+                let fse = vmfs_module.entry(inode.0, ctx.clone());
+
+                fs.insert(inode.0, fse);
+                module_folder.children.push(inode.0);
+            }
+
+            let module_folder_inode = module_folder.inode;
+            fs.insert(module_folder_inode, VirtualEntry::Folder(module_folder));
+            modules_folder.children.push(module_folder_inode);
+        }
+
+        fs.insert(modules_folder_inode, VirtualEntry::Folder(modules_folder));
+        modules_folder_inode
+    }
 }
 
-impl Filesystem for VirtualMemoryFileSystem {
-    fn lookup(&mut self, _req: &Request, parent: u64, name: &OsStr, reply: ReplyEntry) {
+/// Transport-agnostic attributes of a vmfs entry, addressed by its
+/// FUSE-numbered inode (vmfs inode + 1). Shared between the libfuse
+/// transport (the `Filesystem` impl below) and the virtio-fs transport
+/// (the `virtiofs` module), so inode resolution only lives in one place.
+pub struct VmfsAttr {
+    pub ino: u64,
+    pub size: u64,
+    pub is_dir: bool,
+    pub perm: u16,
+}
+
+/// A single, transport-agnostic directory entry.
+pub struct VmfsDirEntry {
+    pub ino: u64,
+    pub is_dir: bool,
+    pub name: String,
+}
+
+/// Outcome of a successful `write_at()` lookup (the inode did resolve to a
+/// file). Kept distinct from "unknown inode" so transports can tell a
+/// missing file apart from an existing, read-only one.
+pub enum WriteOutcome {
+    Written(i64),
+    ReadOnly,
+}
+
+impl VirtualMemoryFileSystem {
+    fn attr_of_entry(entry: &VirtualEntry) -> VmfsAttr {
+        match entry {
+            VirtualEntry::Folder(folder) => VmfsAttr {
+                ino: 1 + folder.inode,
+                size: 0,
+                is_dir: true,
+                perm: 0o755,
+            },
+            VirtualEntry::File(file) => VmfsAttr {
+                ino: 1 + file.inode,
+                size: (file.content_length)(),
+                is_dir: false,
+                perm: file_perm(file),
+            },
+        }
+    }
+
+    /// Resolves the attributes of the vmfs entry at `ino` (FUSE-numbered).
+    pub(crate) fn attr(&mut self, ino: u64) -> Option<VmfsAttr> {
         self.update_file_system();
+        self.file_system.get(&(ino - 1)).map(Self::attr_of_entry)
+    }
 
-        if let Some(entry) = self.file_system.get(&(parent - 1)) {
-            info!(
-                "lookup(): found file system entry: {} {}",
-                entry.inode(),
-                entry.name()
-            );
+    /// Resolves the attributes of the child of `parent` (FUSE-numbered) named `name`.
+    pub(crate) fn lookup_child(&mut self, parent: u64, name: &str) -> Option<VmfsAttr> {
+        self.update_file_system();
 
-            match entry {
-                VirtualEntry::Folder(folder) => {
-                    // match child entries by name
-                    // TODO: maybe add a map?
-                    for child in folder.children.iter() {
-                        if let Some(child_entry) = self.file_system.get(&child) {
-                            // TODO: improve this check
-                            if child_entry.name() == name.to_string_lossy() {
-                                trace!(
-                                    "lookup(): found child entry: {} {}",
-                                    child_entry.inode(),
-                                    child_entry.name()
-                                );
-                                match child_entry {
-                                    VirtualEntry::Folder(child_folder) => {
-                                        reply.entry(
-                                            &TTL,
-                                            &FileAttr {
-                                                ino: 1 + child_folder.inode,
-                                                size: 0,
-                                                blocks: 0,
-                                                atime: UNIX_EPOCH,
-                                                mtime: UNIX_EPOCH,
-                                                ctime: UNIX_EPOCH,
-                                                crtime: UNIX_EPOCH,
-                                                kind: FileType::Directory,
-                                                perm: 0o755,
-                                                nlink: 2,
-                                                uid: self.uid,
-                                                gid: self.gid,
-                                                rdev: 0,
-                                                flags: 0,
-                                            },
-                                            0,
-                                        );
-                                    }
-                                    VirtualEntry::File(child_file) => {
-                                        reply.entry(
-                                            &TTL,
-                                            &FileAttr {
-                                                ino: 1 + child_file.inode,
-                                                size: (child_file.content_length)(),
-                                                blocks: 1, // TODO:
-                                                atime: UNIX_EPOCH,
-                                                mtime: UNIX_EPOCH,
-                                                ctime: UNIX_EPOCH,
-                                                crtime: UNIX_EPOCH,
-                                                kind: FileType::RegularFile,
-                                                perm: 0o644,
-                                                nlink: 1,
-                                                uid: self.uid,
-                                                gid: self.gid,
-                                                rdev: 0,
-                                                flags: 0,
-                                            },
-                                            0,
-                                        );
-                                    }
-                                }
-
-                                // early return, we found our entry
-                                return;
-                            }
-                        }
+        let folder = match self.file_system.get(&(parent - 1))? {
+            VirtualEntry::Folder(folder) => folder,
+            VirtualEntry::File(_) => return None,
+        };
+
+        folder
+            .children
+            .iter()
+            .filter_map(|child| self.file_system.get(child))
+            .find(|child_entry| child_entry.name() == name)
+            .map(Self::attr_of_entry)
+    }
+
+    /// Lists the children of the folder at `ino` (FUSE-numbered); `.`/`..` excluded.
+    pub(crate) fn entries(&mut self, ino: u64) -> Option<Vec<VmfsDirEntry>> {
+        self.update_file_system();
+
+        let folder = match self.file_system.get(&(ino - 1))? {
+            VirtualEntry::Folder(folder) => folder,
+            VirtualEntry::File(_) => return None,
+        };
+
+        Some(
+            folder
+                .children
+                .iter()
+                .filter_map(|child| self.file_system.get(child))
+                .map(|child_entry| {
+                    let attr = Self::attr_of_entry(child_entry);
+                    VmfsDirEntry {
+                        ino: attr.ino,
+                        is_dir: attr.is_dir,
+                        name: child_entry.name().to_string(),
                     }
-                }
-                VirtualEntry::File(_) => {
-                    // TODO: should not happen in readdir - print warn
-                    reply.error(ENOENT);
+                })
+                .collect(),
+        )
+    }
+
+    /// Reads up to `size` bytes at `offset` from the file at `ino` (FUSE-numbered).
+    /// Returns `None` for folders or unknown inodes; `Some(Err(_))` surfaces a
+    /// real I/O failure from the file's `contents` callback (e.g. a `mem` read
+    /// that hit an unmapped page) - callers should map that to `EIO` rather
+    /// than treating it as a successful, truncated read.
+    pub(crate) fn read_at(&mut self, ino: u64, offset: u64, size: u32) -> Option<io::Result<Vec<u8>>> {
+        match self.file_system.get(&(ino - 1))? {
+            VirtualEntry::Folder(_) => None,
+            VirtualEntry::File(file) => {
+                let content_length = (file.content_length)();
+                if offset >= content_length {
+                    Some(Ok(Vec::new()))
+                } else {
+                    let size = (size as u64).min(content_length - offset) as u32;
+                    Some((file.contents)(offset, size))
                 }
             }
-        } else {
-            reply.error(ENOENT);
         }
     }
 
-    fn getattr(&mut self, _req: &Request, ino: u64, reply: ReplyAttr) {
-        self.update_file_system();
+    /// Writes `data` at `offset` into the file at `ino` (FUSE-numbered).
+    /// Returns `None` for folders and unknown inodes - callers should map
+    /// that to `ENOENT`. `Some(Ok(WriteOutcome::ReadOnly))` is returned
+    /// separately for a file that exists but has no writer, so callers can
+    /// report `EACCES` instead of claiming the file doesn't exist.
+    /// `Some(Err(_))` surfaces a real I/O failure from the file's `write`
+    /// callback, which callers should map to `EIO`.
+    pub(crate) fn write_at(
+        &mut self,
+        ino: u64,
+        offset: u64,
+        data: &[u8],
+    ) -> Option<io::Result<WriteOutcome>> {
+        match self.file_system.get(&(ino - 1))? {
+            VirtualEntry::Folder(_) => None,
+            VirtualEntry::File(file) => Some(match &file.write {
+                Some(write) => write(offset, data).map(WriteOutcome::Written),
+                None => Ok(WriteOutcome::ReadOnly),
+            }),
+        }
+    }
 
-        if let Some(entry) = self.file_system.get(&(ino - 1)) {
-            info!(
-                "getattr(): found file system entry: {} {}",
-                entry.inode(),
-                entry.name()
-            );
+    fn fuse_attr(&self, attr: &VmfsAttr) -> FileAttr {
+        FileAttr {
+            ino: attr.ino,
+            size: attr.size,
+            blocks: if attr.is_dir { 0 } else { 1 },
+            atime: UNIX_EPOCH,
+            mtime: UNIX_EPOCH,
+            ctime: UNIX_EPOCH,
+            crtime: UNIX_EPOCH,
+            kind: if attr.is_dir {
+                FileType::Directory
+            } else {
+                FileType::RegularFile
+            },
+            perm: attr.perm,
+            nlink: if attr.is_dir { 2 } else { 1 },
+            uid: self.uid,
+            gid: self.gid,
+            rdev: 0,
+            flags: 0,
+        }
+    }
+}
 
-            match entry {
-                VirtualEntry::Folder(folder) => {
-                    reply.attr(
-                        &TTL,
-                        &FileAttr {
-                            ino: 1 + folder.inode,
-                            size: 0,
-                            blocks: 0,
-                            atime: UNIX_EPOCH,
-                            mtime: UNIX_EPOCH,
-                            ctime: UNIX_EPOCH,
-                            crtime: UNIX_EPOCH,
-                            kind: FileType::Directory,
-                            perm: 0o755,
-                            nlink: 2,
-                            uid: self.uid,
-                            gid: self.gid,
-                            rdev: 0,
-                            flags: 0,
-                        },
-                    );
-                }
-                VirtualEntry::File(file) => {
-                    reply.attr(
-                        &TTL,
-                        &FileAttr {
-                            ino: 1 + file.inode,
-                            size: 13,  // TODO:
-                            blocks: 1, // TODO:
-                            atime: UNIX_EPOCH,
-                            mtime: UNIX_EPOCH,
-                            ctime: UNIX_EPOCH,
-                            crtime: UNIX_EPOCH,
-                            kind: FileType::RegularFile,
-                            perm: 0o644,
-                            nlink: 1,
-                            uid: self.uid,
-                            gid: self.gid,
-                            rdev: 0,
-                            flags: 0,
-                        },
-                    );
-                }
+impl Filesystem for VirtualMemoryFileSystem {
+    fn lookup(&mut self, _req: &Request, parent: u64, name: &OsStr, reply: ReplyEntry) {
+        match self.lookup_child(parent, &name.to_string_lossy()) {
+            Some(attr) => {
+                trace!("lookup(): found entry: {}", attr.ino);
+                let fuse_attr = self.fuse_attr(&attr);
+                reply.entry(&TTL, &fuse_attr, 0);
             }
-        } else {
-            reply.error(ENOENT);
+            None => reply.error(ENOENT),
+        }
+    }
+
+    fn getattr(&mut self, _req: &Request, ino: u64, reply: ReplyAttr) {
+        match self.attr(ino) {
+            Some(attr) => {
+                let fuse_attr = self.fuse_attr(&attr);
+                reply.attr(&TTL, &fuse_attr);
+            }
+            None => reply.error(ENOENT),
         }
     }
 
@@ -361,34 +671,42 @@ impl Filesystem for VirtualMemoryFileSystem {
         ino: u64,
         _fh: u64,
         offset: i64,
-        _size: u32,
+        size: u32,
         reply: ReplyData,
     ) {
-        println!(
-            "read: ino={}, fh={}, offset={} size={}",
-            ino, _fh, offset, _size
+        trace!(
+            "read(): ino={}, fh={}, offset={} size={}",
+            ino, _fh, offset, size
         );
 
-        if let Some(entry) = self.file_system.get(&(ino - 1)) {
-            info!(
-                "getattr(): found file system entry: {} {}",
-                entry.inode(),
-                entry.name()
-            );
+        match self.read_at(ino, offset as u64, size) {
+            Some(Ok(data)) => reply.data(&data),
+            Some(Err(err)) => reply.error(err.raw_os_error().unwrap_or(EIO)),
+            None => reply.error(ENOENT),
+        }
+    }
 
-            match entry {
-                VirtualEntry::Folder(_folder) => {
-                    // should not happen
-                    reply.error(ENOENT);
-                }
-                VirtualEntry::File(file) => {
-                    // get file contents :)
-                    let contents = (file.contents)();
-                    reply.data(&contents.as_slice()[offset as usize..]);
-                }
-            }
-        } else {
-            reply.error(ENOENT);
+    fn write(
+        &mut self,
+        _req: &Request,
+        ino: u64,
+        _fh: u64,
+        offset: i64,
+        data: &[u8],
+        _write_flags: u32,
+        reply: ReplyWrite,
+    ) {
+        trace!("write(): ino={}, offset={} size={}", ino, offset, data.len());
+
+        match self.attr(ino) {
+            Some(attr) if attr.is_dir => reply.error(EISDIR),
+            Some(_) => match self.write_at(ino, offset as u64, data) {
+                Some(Ok(WriteOutcome::Written(written))) => reply.written(written as u32),
+                Some(Ok(WriteOutcome::ReadOnly)) => reply.error(EACCES),
+                Some(Err(err)) => reply.error(err.raw_os_error().unwrap_or(EIO)),
+                None => reply.error(ENOENT),
+            },
+            None => reply.error(ENOENT),
         }
     }
 
@@ -400,70 +718,35 @@ impl Filesystem for VirtualMemoryFileSystem {
         offset: i64,
         mut reply: ReplyDirectory,
     ) {
-        self.update_file_system();
-
-        if let Some(entry) = self.file_system.get(&(ino - 1)) {
-            info!(
-                "readdir(): found file system entry: {} {}",
-                entry.inode(),
-                entry.name()
-            );
-
-            match entry {
-                VirtualEntry::Folder(folder) => {
-                    let mut entries = vec![
-                        (1, FileType::Directory, ".".to_string()),
-                        (1, FileType::Directory, "..".to_string()),
-                    ];
-
-                    // find each child entry and add them to the list
-                    for child in folder.children.iter() {
-                        if let Some(child_entry) = self.file_system.get(&child) {
-                            match child_entry {
-                                VirtualEntry::Folder(child_folder) => {
-                                    trace!(
-                                        "readdir(): adding child folder: {} {}",
-                                        child_folder.inode,
-                                        child_folder.name
-                                    );
-                                    entries.push((
-                                        1 + child_folder.inode,
-                                        FileType::Directory,
-                                        child_folder.name.clone(),
-                                    ));
-                                }
-                                VirtualEntry::File(child_file) => {
-                                    trace!(
-                                        "readdir(): adding child file: {} {}",
-                                        child_file.inode,
-                                        child_file.name
-                                    );
-                                    entries.push((
-                                        1 + child_file.inode,
-                                        FileType::RegularFile,
-                                        child_file.name.clone(),
-                                    ));
-                                }
-                            }
-                        }
-                    }
+        let children = match self.entries(ino) {
+            Some(children) => children,
+            None => {
+                reply.error(ENOENT);
+                return;
+            }
+        };
 
-                    // send entries to fuse
-                    for (i, entry) in entries.into_iter().enumerate().skip(offset as usize) {
-                        // i + 1 means the index of the next entry
-                        reply.add(entry.0, (i + 1) as i64, entry.1, entry.2);
-                    }
+        let mut entries = vec![
+            (1, FileType::Directory, ".".to_string()),
+            (1, FileType::Directory, "..".to_string()),
+        ];
+
+        for child in children {
+            let kind = if child.is_dir {
+                FileType::Directory
+            } else {
+                FileType::RegularFile
+            };
+            entries.push((child.ino, kind, child.name));
+        }
 
-                    reply.ok();
-                }
-                VirtualEntry::File(_) => {
-                    // TODO: should not happen in readdir - print warn
-                    reply.error(ENOENT);
-                }
-            }
-        } else {
-            reply.error(ENOENT);
+        // send entries to fuse
+        for (i, entry) in entries.into_iter().enumerate().skip(offset as usize) {
+            // i + 1 means the index of the next entry
+            reply.add(entry.0, (i + 1) as i64, entry.1, entry.2);
         }
+
+        reply.ok();
     }
 }
 
@@ -493,4 +776,55 @@ impl Drop for VirtualMemoryFileSystem {
             }
         });
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn modules_key(modules: &[(u64, u64, &str)]) -> ModulesKey {
+        modules
+            .iter()
+            .map(|(base, size, name)| (*base, *size, name.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn diff_stale_pids_finds_pids_no_longer_live() {
+        let live: HashSet<i32> = [1, 2].iter().copied().collect();
+        let mut stale = diff_stale_pids(&live, vec![1, 2, 3, 4].into_iter());
+        stale.sort_unstable();
+        assert_eq!(stale, vec![3, 4]);
+    }
+
+    #[test]
+    fn diff_stale_pids_empty_when_all_live() {
+        let live: HashSet<i32> = [1, 2, 3].iter().copied().collect();
+        let stale = diff_stale_pids(&live, vec![1, 2].into_iter());
+        assert!(stale.is_empty());
+    }
+
+    #[test]
+    fn classify_pid_new_when_never_tracked() {
+        let new_modules = modules_key(&[(0x1000, 0x10, "a.dll")]);
+        assert_eq!(classify_pid(None, None, &new_modules), PidAction::New);
+    }
+
+    #[test]
+    fn classify_pid_unchanged_when_modules_match() {
+        let modules = modules_key(&[(0x1000, 0x10, "a.dll")]);
+        assert_eq!(
+            classify_pid(Some(42), Some(&modules), &modules),
+            PidAction::Unchanged
+        );
+    }
+
+    #[test]
+    fn classify_pid_changed_when_modules_differ() {
+        let old_modules = modules_key(&[(0x1000, 0x10, "a.dll")]);
+        let new_modules = modules_key(&[(0x1000, 0x10, "a.dll"), (0x2000, 0x20, "b.dll")]);
+        assert_eq!(
+            classify_pid(Some(42), Some(&old_modules), &new_modules),
+            PidAction::Changed(42)
+        );
+    }
+}
@@ -0,0 +1,68 @@
+use super::backend::VmfsProcessSource;
+use super::{VMFSModule, VMFSModuleScope, VMFSScopeContext, VirtualEntry, VirtualFile};
+use crate::state::state_lock_sync;
+
+/// Renders the `maps` file contents for a single process scope.
+/// Mirrors the layout of Linux's `/proc/<pid>/maps`: one loaded module per
+/// line, formatted as `base-end module`. Only the address ranges of loaded
+/// modules are listed; regions not backed by a module (heaps, stacks, other
+/// private allocations) do not appear. Unlike `/proc/<pid>/maps`, there's
+/// no protection-flags column - `ModInfo` carries no protection bits, and
+/// printing a made-up one would be worse than omitting it.
+fn render(ctx: &VMFSScopeContext) -> Vec<u8> {
+    let (conn_id, pid) = match ctx {
+        VMFSScopeContext::Process { conn_id, pid } => (conn_id.clone(), *pid),
+        _ => return Vec::new(),
+    };
+
+    let mut state = state_lock_sync();
+    let conn = match state.connection_mut(&conn_id) {
+        Some(conn) => conn,
+        None => return Vec::new(),
+    };
+
+    let modules = match conn.kernel.module_list(pid) {
+        Ok(modules) => modules,
+        Err(_) => return Vec::new(),
+    };
+
+    let mut out = String::new();
+    for module in modules.iter() {
+        out.push_str(&format!(
+            "{:x}-{:x} {}\n",
+            module.base,
+            module.base + module.size,
+            module.name,
+        ));
+    }
+
+    out.into_bytes()
+}
+
+/// Lists the address ranges of the process's loaded modules, similar to
+/// `/proc/<pid>/maps`; memory not backed by a module is not listed.
+pub struct VMFSMaps;
+
+impl VMFSModule for VMFSMaps {
+    fn scope(&self) -> VMFSModuleScope {
+        VMFSModuleScope::Process
+    }
+
+    fn entry(&self, inode: u64, ctx: VMFSScopeContext) -> VirtualEntry {
+        let ctx1 = ctx.clone();
+        let ctx2 = ctx;
+
+        VirtualEntry::File(VirtualFile {
+            inode,
+            name: "maps".to_string(),
+            content_length: Box::new(move || render(&ctx1).len() as u64),
+            contents: Box::new(move |offset, size| {
+                let content = render(&ctx2);
+                let start = (offset as usize).min(content.len());
+                let end = (start + size as usize).min(content.len());
+                Ok(content[start..end].to_vec())
+            }),
+            write: None,
+        })
+    }
+}
@@ -0,0 +1,63 @@
+use super::backend::VmfsProcessSource;
+use super::{VMFSModule, VMFSModuleScope, VMFSScopeContext, VirtualEntry, VirtualFile};
+use crate::state::state_lock_sync;
+
+use std::io;
+
+/// Upper bound reported for the `mem` file's size. A process's virtual
+/// address space isn't a single fixed quantity, so this only needs to be
+/// large enough that tools can seek/read at arbitrary offsets; reads and
+/// writes past what's actually mapped fail the underlying `virt_read`/
+/// `virt_write` call, which is surfaced as a real `EIO` rather than a
+/// successful empty read/write (see `read_mem`/`write_mem`).
+const MEM_FILE_SIZE: u64 = 0x7fff_ffff_ffff;
+
+fn io_err(err: String) -> io::Error {
+    io::Error::new(io::ErrorKind::Other, err)
+}
+
+fn read_mem(conn_id: &str, pid: i32, addr: u64, size: u32) -> io::Result<Vec<u8>> {
+    let mut state = state_lock_sync();
+    match state.connection_mut(conn_id) {
+        Some(conn) => conn.kernel.read(pid, addr, size).map_err(io_err),
+        None => Err(io_err(format!("no such connection: {}", conn_id))),
+    }
+}
+
+fn write_mem(conn_id: &str, pid: i32, addr: u64, data: &[u8]) -> io::Result<i64> {
+    let mut state = state_lock_sync();
+    match state.connection_mut(conn_id) {
+        Some(conn) => conn.kernel.write(pid, addr, data).map_err(io_err),
+        None => Err(io_err(format!("no such connection: {}", conn_id))),
+    }
+}
+
+/// Raw process memory, readable and writable at arbitrary (offset, size)
+/// windows - `offset` is interpreted directly as a virtual address.
+pub struct VMFSMem;
+
+impl VMFSModule for VMFSMem {
+    fn scope(&self) -> VMFSModuleScope {
+        VMFSModuleScope::Process
+    }
+
+    fn entry(&self, inode: u64, ctx: VMFSScopeContext) -> VirtualEntry {
+        let (conn_id, pid) = match &ctx {
+            VMFSScopeContext::Process { conn_id, pid } => (conn_id.clone(), *pid),
+            _ => (String::new(), 0),
+        };
+
+        let read_conn_id = conn_id.clone();
+        let write_conn_id = conn_id;
+
+        VirtualEntry::File(VirtualFile {
+            inode,
+            name: "mem".to_string(),
+            content_length: Box::new(|| MEM_FILE_SIZE),
+            contents: Box::new(move |offset, size| read_mem(&read_conn_id, pid, offset, size)),
+            write: Some(Box::new(move |offset, data| {
+                write_mem(&write_conn_id, pid, offset, data)
+            })),
+        })
+    }
+}
@@ -0,0 +1,134 @@
+use super::backend::{ModInfo, VmfsProcessSource};
+use super::{VMFSModule, VMFSModuleScope, VMFSScopeContext, VirtualEntry, VirtualFile};
+use crate::state::state_lock_sync;
+
+/// Looks up the module at `base` for the given process at read-time.
+/// Identifying the module by its base address (rather than its position in
+/// a freshly-fetched `module_list()`) is deliberate: the kernel's module
+/// enumeration order isn't guaranteed stable across calls even when the
+/// module set itself hasn't changed, so a positional index can silently
+/// resolve to the wrong module after such a reorder. A base address is
+/// stable for as long as the module stays loaded, which is exactly the
+/// lifetime of the folder that carries this context (see
+/// `create_modules_folder`).
+fn find_module(conn_id: &str, pid: i32, base: u64) -> Option<ModInfo> {
+    let mut state = state_lock_sync();
+    let conn = state.connection_mut(conn_id)?;
+    conn.kernel
+        .module_list(pid)
+        .ok()?
+        .into_iter()
+        .find(|module| module.base == base)
+}
+
+fn ctx_parts(ctx: &VMFSScopeContext) -> Option<(String, i32, u64)> {
+    match ctx {
+        VMFSScopeContext::Module { conn_id, pid, base } => Some((conn_id.clone(), *pid, *base)),
+        _ => None,
+    }
+}
+
+/// The module's base address, in hex, terminated with a newline.
+fn render_base(ctx: &VMFSScopeContext) -> Vec<u8> {
+    ctx_parts(ctx)
+        .and_then(|(conn_id, pid, base)| find_module(&conn_id, pid, base))
+        .map(|module| format!("{:x}\n", module.base).into_bytes())
+        .unwrap_or_default()
+}
+
+/// The module's size in bytes, decimal, terminated with a newline.
+fn render_size(ctx: &VMFSScopeContext) -> Vec<u8> {
+    ctx_parts(ctx)
+        .and_then(|(conn_id, pid, base)| find_module(&conn_id, pid, base))
+        .map(|module| format!("{}\n", module.size).into_bytes())
+        .unwrap_or_default()
+}
+
+/// The module's name, terminated with a newline.
+fn render_name(ctx: &VMFSScopeContext) -> Vec<u8> {
+    ctx_parts(ctx)
+        .and_then(|(conn_id, pid, base)| find_module(&conn_id, pid, base))
+        .map(|module| format!("{}\n", module.name).into_bytes())
+        .unwrap_or_default()
+}
+
+/// `base` file of a module folder - the module's base address.
+pub struct VMFSModuleBase;
+
+impl VMFSModule for VMFSModuleBase {
+    fn scope(&self) -> VMFSModuleScope {
+        VMFSModuleScope::Module
+    }
+
+    fn entry(&self, inode: u64, ctx: VMFSScopeContext) -> VirtualEntry {
+        let ctx1 = ctx.clone();
+        let ctx2 = ctx;
+
+        VirtualEntry::File(VirtualFile {
+            inode,
+            name: "base".to_string(),
+            content_length: Box::new(move || render_base(&ctx1).len() as u64),
+            contents: Box::new(move |offset, size| {
+                let content = render_base(&ctx2);
+                let start = (offset as usize).min(content.len());
+                let end = (start + size as usize).min(content.len());
+                Ok(content[start..end].to_vec())
+            }),
+            write: None,
+        })
+    }
+}
+
+/// `size` file of a module folder - the module's size in bytes.
+pub struct VMFSModuleSize;
+
+impl VMFSModule for VMFSModuleSize {
+    fn scope(&self) -> VMFSModuleScope {
+        VMFSModuleScope::Module
+    }
+
+    fn entry(&self, inode: u64, ctx: VMFSScopeContext) -> VirtualEntry {
+        let ctx1 = ctx.clone();
+        let ctx2 = ctx;
+
+        VirtualEntry::File(VirtualFile {
+            inode,
+            name: "size".to_string(),
+            content_length: Box::new(move || render_size(&ctx1).len() as u64),
+            contents: Box::new(move |offset, size| {
+                let content = render_size(&ctx2);
+                let start = (offset as usize).min(content.len());
+                let end = (start + size as usize).min(content.len());
+                Ok(content[start..end].to_vec())
+            }),
+            write: None,
+        })
+    }
+}
+
+/// `name` file of a module folder - the module's name.
+pub struct VMFSModuleName;
+
+impl VMFSModule for VMFSModuleName {
+    fn scope(&self) -> VMFSModuleScope {
+        VMFSModuleScope::Module
+    }
+
+    fn entry(&self, inode: u64, ctx: VMFSScopeContext) -> VirtualEntry {
+        let ctx1 = ctx.clone();
+        let ctx2 = ctx;
+
+        VirtualEntry::File(VirtualFile {
+            inode,
+            name: "name".to_string(),
+            content_length: Box::new(move || render_name(&ctx1).len() as u64),
+            contents: Box::new(move |offset, size| {
+                let content = render_name(&ctx2);
+                let start = (offset as usize).min(content.len());
+                let end = (start + size as usize).min(content.len());
+                Ok(content[start..end].to_vec())
+            }),
+            write: None,
+        })
+    }
+}
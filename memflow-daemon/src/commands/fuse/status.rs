@@ -0,0 +1,57 @@
+use super::backend::VmfsProcessSource;
+use super::{VMFSModule, VMFSModuleScope, VMFSScopeContext, VirtualEntry, VirtualFile};
+use crate::state::state_lock_sync;
+
+/// Renders the `status` file contents for a single process scope.
+/// Mirrors the layout of Linux's `/proc/<pid>/status` (one `key:\tvalue` per line).
+fn render(ctx: &VMFSScopeContext) -> Vec<u8> {
+    let (conn_id, pid) = match ctx {
+        VMFSScopeContext::Process { conn_id, pid } => (conn_id.clone(), *pid),
+        _ => return Vec::new(),
+    };
+
+    let mut state = state_lock_sync();
+    let conn = match state.connection_mut(&conn_id) {
+        Some(conn) => conn,
+        None => return Vec::new(),
+    };
+
+    let processes = match conn.kernel.process_list() {
+        Ok(processes) => processes,
+        Err(_) => return Vec::new(),
+    };
+
+    match processes.into_iter().find(|pi| pi.pid == pid) {
+        Some(pi) => format!("Pid:\t{}\nName:\t{}\nArch:\t{}\n", pi.pid, pi.name, pi.arch).into_bytes(),
+        None => Vec::new(),
+    }
+}
+
+// NOTE: no PPid line - `ProcInfo`/the Win32 kernel API expose no parent pid,
+// and printing the process's own pid under that label would be fabricated data.
+/// Summarizes pid/name/architecture for a process, similar to `/proc/<pid>/status`.
+pub struct VMFSStatus;
+
+impl VMFSModule for VMFSStatus {
+    fn scope(&self) -> VMFSModuleScope {
+        VMFSModuleScope::Process
+    }
+
+    fn entry(&self, inode: u64, ctx: VMFSScopeContext) -> VirtualEntry {
+        let ctx1 = ctx.clone();
+        let ctx2 = ctx;
+
+        VirtualEntry::File(VirtualFile {
+            inode,
+            name: "status".to_string(),
+            content_length: Box::new(move || render(&ctx1).len() as u64),
+            contents: Box::new(move |offset, size| {
+                let content = render(&ctx2);
+                let start = (offset as usize).min(content.len());
+                let end = (start + size as usize).min(content.len());
+                Ok(content[start..end].to_vec())
+            }),
+            write: None,
+        })
+    }
+}
@@ -0,0 +1,308 @@
+//! Serves a `VirtualMemoryFileSystem` to guest VMs as a vhost-user virtio-fs
+//! device (the virtiofsd model), as an alternative to the local libfuse mount.
+//!
+//! Unlike the libfuse transport, vhost-user doesn't drive a `FileSystem` impl
+//! directly. A `fuse_backend_rs::api::server::Server<FS>` turns raw FUSE wire
+//! buffers into calls against `VirtioFsServer`; a separate `VhostUserBackend`
+//! impl (`VirtioFsBackend`) is what `vhost-user-backend` actually drives - it
+//! owns the vrings, and on each queue kick decodes the guest's descriptor
+//! chains into a `Reader`/`Writer` pair and hands them to
+//! `Server::handle_message`. The inode resolution itself lives on
+//! `VirtualMemoryFileSystem` (`attr`, `lookup_child`, `entries`, `read_at`,
+//! `write_at`) and is shared with the libfuse transport in `filesystem.rs`;
+//! this module only adapts that resolution to the virtio-fs wire protocol.
+
+use super::{VirtualMemoryFileSystem, VmfsAttr, WriteOutcome};
+
+use std::io;
+use std::sync::{Arc, Mutex};
+
+use fuse_backend_rs::api::filesystem::{
+    Context, Entry, FileSystem, FsOptions, ZeroCopyReader, ZeroCopyWriter,
+};
+use fuse_backend_rs::api::server::Server;
+use fuse_backend_rs::transport::{Reader, Writer};
+use vhost_user_backend::{VhostUserBackend, VhostUserDaemon, VringMutex, VringT};
+use virtio_queue::QueueT;
+use vm_memory::{GuestMemoryAtomic, GuestMemoryMmap};
+
+use log::{error, info, warn};
+
+/// virtiofsd convention: one high-priority queue (not used here - there's no
+/// notification path to speak of) plus one request queue carrying FUSE
+/// messages.
+const NUM_QUEUES: usize = 2;
+const REQUEST_QUEUE: u16 = 1;
+const QUEUE_SIZE: usize = 1024;
+
+fn entry_attr(attr: &VmfsAttr) -> Entry {
+    // SAFETY: a zeroed `stat64` is a valid (if mostly empty) representation;
+    // we only fill in the fields the virtio-fs client actually inspects.
+    let mut stat: libc::stat64 = unsafe { std::mem::zeroed() };
+    stat.st_ino = attr.ino;
+    stat.st_size = attr.size as i64;
+    stat.st_mode = (if attr.is_dir { libc::S_IFDIR } else { libc::S_IFREG }) | attr.perm as u32;
+    stat.st_nlink = if attr.is_dir { 2 } else { 1 };
+
+    Entry {
+        inode: attr.ino,
+        generation: 0,
+        attr: stat,
+        attr_flags: 0,
+        attr_timeout: super::TTL,
+        entry_timeout: super::TTL,
+    }
+}
+
+/// Adapts a `VirtualMemoryFileSystem` to `fuse-backend-rs`'s `FileSystem`
+/// trait so it can be driven by a `Server` instead of the kernel's
+/// `/dev/fuse`. `Mutex`-wrapped because `Server` drives requests from
+/// whichever thread calls `handle_message`, while `VirtualMemoryFileSystem`
+/// itself is only `Send`, not `Sync`.
+pub struct VirtioFsServer {
+    vmfs: Mutex<VirtualMemoryFileSystem>,
+}
+
+impl VirtioFsServer {
+    pub fn new(vmfs: VirtualMemoryFileSystem) -> Self {
+        Self {
+            vmfs: Mutex::new(vmfs),
+        }
+    }
+}
+
+impl FileSystem for VirtioFsServer {
+    type Inode = u64;
+    type Handle = u64;
+
+    fn init(&self, _capable: FsOptions) -> io::Result<FsOptions> {
+        Ok(FsOptions::empty())
+    }
+
+    fn lookup(&self, _ctx: &Context, parent: Self::Inode, name: &std::ffi::CStr) -> io::Result<Entry> {
+        self.vmfs
+            .lock()
+            .unwrap()
+            .lookup_child(parent, &name.to_string_lossy())
+            .map(|attr| entry_attr(&attr))
+            .ok_or_else(|| io::Error::from_raw_os_error(libc::ENOENT))
+    }
+
+    fn getattr(
+        &self,
+        _ctx: &Context,
+        inode: Self::Inode,
+        _handle: Option<Self::Handle>,
+    ) -> io::Result<(libc::stat64, std::time::Duration)> {
+        self.vmfs
+            .lock()
+            .unwrap()
+            .attr(inode)
+            .map(|attr| (entry_attr(&attr).attr, super::TTL))
+            .ok_or_else(|| io::Error::from_raw_os_error(libc::ENOENT))
+    }
+
+    fn read(
+        &self,
+        _ctx: &Context,
+        inode: Self::Inode,
+        _handle: Self::Handle,
+        w: &mut dyn ZeroCopyWriter,
+        size: u32,
+        offset: u64,
+        _lock_owner: Option<u64>,
+        _flags: u32,
+    ) -> io::Result<usize> {
+        let data = self
+            .vmfs
+            .lock()
+            .unwrap()
+            .read_at(inode, offset, size)
+            .ok_or_else(|| io::Error::from_raw_os_error(libc::ENOENT))??;
+
+        w.write(&data)
+    }
+
+    fn write(
+        &self,
+        _ctx: &Context,
+        inode: Self::Inode,
+        _handle: Self::Handle,
+        r: &mut dyn ZeroCopyReader,
+        size: u32,
+        offset: u64,
+        _lock_owner: Option<u64>,
+        _delayed_write: bool,
+        _flags: u32,
+        _fuse_flags: u32,
+    ) -> io::Result<usize> {
+        let mut data = vec![0u8; size as usize];
+        r.read_exact(&mut data)?;
+
+        match self.vmfs.lock().unwrap().write_at(inode, offset, &data) {
+            Some(Ok(WriteOutcome::Written(written))) => Ok(written as usize),
+            Some(Ok(WriteOutcome::ReadOnly)) => Err(io::Error::from_raw_os_error(libc::EACCES)),
+            Some(Err(err)) => Err(err),
+            None => Err(io::Error::from_raw_os_error(libc::ENOENT)),
+        }
+    }
+
+    fn readdir(
+        &self,
+        _ctx: &Context,
+        inode: Self::Inode,
+        _handle: Self::Handle,
+        _size: u32,
+        offset: u64,
+        add_entry: &mut dyn FnMut(fuse_backend_rs::api::filesystem::DirEntry) -> io::Result<usize>,
+    ) -> io::Result<()> {
+        let entries = self
+            .vmfs
+            .lock()
+            .unwrap()
+            .entries(inode)
+            .ok_or_else(|| io::Error::from_raw_os_error(libc::ENOENT))?;
+
+        for (i, entry) in entries.into_iter().enumerate().skip(offset as usize) {
+            let dir_entry = fuse_backend_rs::api::filesystem::DirEntry {
+                ino: entry.ino,
+                offset: (i + 1) as u64,
+                type_: if entry.is_dir { libc::DT_DIR } else { libc::DT_REG } as u32,
+                name: entry.name.as_bytes(),
+            };
+            if add_entry(dir_entry)? == 0 {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Drives the vhost-user request queue for a `VirtioFsServer`: on every
+/// queue kick, decodes the guest's descriptor chains into a `Reader`/`Writer`
+/// pair over guest memory and hands them to `Server::handle_message`, which
+/// does the actual FUSE opcode dispatch against `fuse_server`.
+struct VirtioFsBackend {
+    fuse_server: Arc<Server<VirtioFsServer>>,
+    mem: Option<GuestMemoryAtomic<GuestMemoryMmap>>,
+}
+
+impl VirtioFsBackend {
+    fn new(fuse_server: Arc<Server<VirtioFsServer>>) -> Self {
+        Self {
+            fuse_server,
+            mem: None,
+        }
+    }
+
+    fn process_queue(&self, vring: &VringMutex) -> io::Result<()> {
+        let mem = self
+            .mem
+            .as_ref()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::Other, "backend not activated yet"))?
+            .memory();
+
+        while let Some(chain) = vring
+            .get_queue_mut()
+            .pop_descriptor_chain(mem.clone())
+        {
+            let head_index = chain.head_index();
+            let reader = Reader::from_descriptor_chain(&mem, chain.clone())
+                .map_err(|err| io::Error::new(io::ErrorKind::Other, format!("{:?}", err)))?;
+            let writer = Writer::from_descriptor_chain(&mem, chain)
+                .map_err(|err| io::Error::new(io::ErrorKind::Other, format!("{:?}", err)))?;
+
+            let written = self
+                .fuse_server
+                .handle_message(reader, writer)
+                .unwrap_or_else(|err| {
+                    warn!("virtiofs: failed to handle FUSE message: {:?}", err);
+                    0
+                });
+
+            vring
+                .get_queue_mut()
+                .add_used(&*mem, head_index, written as u32)
+                .map_err(|err| io::Error::new(io::ErrorKind::Other, format!("{:?}", err)))?;
+        }
+
+        vring.signal_used_queue().map_err(|err| {
+            io::Error::new(io::ErrorKind::Other, format!("failed to signal used queue: {:?}", err))
+        })
+    }
+}
+
+impl VhostUserBackend for VirtioFsBackend {
+    type Bitmap = ();
+    type Vring = VringMutex;
+
+    fn num_queues(&self) -> usize {
+        NUM_QUEUES
+    }
+
+    fn max_queue_size(&self) -> usize {
+        QUEUE_SIZE
+    }
+
+    fn features(&self) -> u64 {
+        1 << virtio_bindings::bindings::virtio_ring::VIRTIO_RING_F_EVENT_IDX
+            | 1 << virtio_bindings::bindings::virtio_config::VIRTIO_F_VERSION_1
+    }
+
+    fn acked_features(&mut self, _features: u64) {}
+
+    fn set_event_idx(&mut self, _enabled: bool) {}
+
+    fn update_memory(&mut self, mem: GuestMemoryAtomic<GuestMemoryMmap>) -> io::Result<()> {
+        self.mem = Some(mem);
+        Ok(())
+    }
+
+    fn handle_event(
+        &mut self,
+        device_event: u16,
+        vrings: &[Self::Vring],
+        _thread_id: usize,
+    ) -> io::Result<()> {
+        if device_event != REQUEST_QUEUE {
+            return Ok(());
+        }
+
+        let vring = vrings
+            .get(REQUEST_QUEUE as usize)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::Other, "missing request vring"))?;
+
+        self.process_queue(vring)
+    }
+}
+
+/// Spawns a vhost-user virtio-fs device serving `vmfs`, listening on the
+/// unix socket at `socket_path`, so a VM can mount the process tree at e.g.
+/// `/mnt/vmfs` directly instead of going through a local libfuse mount.
+pub fn serve(vmfs: VirtualMemoryFileSystem, socket_path: &str) -> io::Result<()> {
+    let fuse_server = Arc::new(Server::new(VirtioFsServer::new(vmfs)));
+    let backend = Arc::new(Mutex::new(VirtioFsBackend::new(fuse_server)));
+
+    info!("starting vhost-user virtio-fs device on {}", socket_path);
+
+    let mut daemon = VhostUserDaemon::new(
+        "memflow-vmfs-virtiofs".to_string(),
+        backend,
+        GuestMemoryAtomic::new(GuestMemoryMmap::from_ranges(&[]).map_err(|err| {
+            io::Error::new(io::ErrorKind::Other, format!("failed to set up guest memory: {:?}", err))
+        })?),
+    )
+    .map_err(|err| {
+        error!("failed to create vhost-user daemon: {:?}", err);
+        io::Error::new(io::ErrorKind::Other, "failed to create vhost-user daemon")
+    })?;
+
+    daemon
+        .start(socket_path)
+        .map_err(|err| io::Error::new(io::ErrorKind::Other, format!("{:?}", err)))?;
+
+    daemon
+        .wait()
+        .map_err(|err| io::Error::new(io::ErrorKind::Other, format!("{:?}", err)))
+}